@@ -0,0 +1,127 @@
+//! Exponential-backoff lockout enforcement.
+
+/// Initial lockout applied after the first failed attempt.
+const BAN_TIME_BASE: i64 = 30;
+/// Upper bound on the exponential backoff.
+const BAN_TIME_CAP: i64 = 3600;
+
+/// Outcome of [`Member::try_enter`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnterDecision {
+    /// Entry is permitted.
+    Allowed,
+    /// Entry is denied; another attempt may succeed once `retry_after` (a Unix timestamp) has
+    /// passed.
+    Denied {
+        /// Unix timestamp after which another attempt may succeed.
+        retry_after: i64,
+    },
+}
+
+impl ::Member {
+    /// Decides whether `self` may enter at `now`, enforcing exponential-backoff lockout.
+    ///
+    /// If `self` is still within a previously-recorded lockout window, returns `Denied` without
+    /// considering `authenticated` (so cloned-tag hammering can't shorten its own ban by
+    /// retrying). Otherwise `authenticated` — the result of whatever credential check the
+    /// caller already performed (e.g. [`::identify_user_challenge`]) — decides the outcome:
+    /// on success the lockout is cleared and `last_enter_time` is set; on failure `ban_time` is
+    /// doubled (starting from a base, capped) and `last_open_attempt` is recorded. Either way the
+    /// updated fields are persisted to `conn`, and the decision is appended to the events log,
+    /// before returning.
+    pub fn try_enter(&mut self, conn: &mut ::rusqlite::Connection, now: i64, authenticated: bool) -> ::rusqlite::Result<EnterDecision> {
+        if let (Some(last_attempt), Some(ban_time)) = (self.last_open_attempt, self.ban_time) {
+            let retry_after = last_attempt + ban_time;
+            if now < retry_after {
+                try!(::events::log_event(conn, self.uid, None, ::events::EventType::Denied, now, Some("locked out")));
+                return Ok(EnterDecision::Denied { retry_after: retry_after });
+            }
+        }
+
+        if authenticated {
+            self.ban_time = None;
+            self.last_open_attempt = None;
+            self.last_enter_time = Some(now);
+            try!(self.update(conn));
+            try!(::events::log_event(conn, self.uid, None, ::events::EventType::Enter, now, None));
+
+            Ok(EnterDecision::Allowed)
+        } else {
+            let ban_time = self.ban_time.map_or(BAN_TIME_BASE, |b| (b * 2).min(BAN_TIME_CAP));
+            self.ban_time = Some(ban_time);
+            self.last_open_attempt = Some(now);
+            try!(self.update(conn));
+            try!(::events::log_event(conn, self.uid, None, ::events::EventType::Denied, now, Some("authentication failed")));
+
+            Ok(EnterDecision::Denied { retry_after: now + ban_time })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnterDecision;
+
+    fn conn_with_tables() -> ::rusqlite::Connection {
+        let mut conn = ::rusqlite::Connection::open_in_memory().unwrap();
+        ::create_tables(&mut conn).unwrap();
+        ::events::create_events_table_impl(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn successful_attempt_clears_lockout_and_persists() {
+        let mut conn = conn_with_tables();
+        let mut member = ::Member {
+            uid: 42,
+            can_manage_users: false,
+            ban_time: None,
+            last_open_attempt: None,
+            max_auto_inactive: 1800,
+            last_enter_time: None,
+            last_leave_time: None,
+        };
+        member.insert(&mut conn).unwrap();
+
+        let decision = member.try_enter(&mut conn, 1000, true).unwrap();
+
+        assert_eq!(decision, EnterDecision::Allowed);
+        assert_eq!(member.ban_time, None);
+        assert_eq!(member.last_enter_time, Some(1000));
+
+        let persisted: i64 = conn.query_row(
+            "SELECT last_enter FROM members WHERE uid = 42", &[], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(persisted, 1000);
+
+        let events = ::events::events_for_member(&conn, 42, 0, 10).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, ::events::EventType::Enter);
+    }
+
+    #[test]
+    fn failed_attempts_back_off_exponentially() {
+        let mut conn = conn_with_tables();
+        let mut member = ::Member {
+            uid: 42,
+            can_manage_users: false,
+            ban_time: None,
+            last_open_attempt: None,
+            max_auto_inactive: 1800,
+            last_enter_time: None,
+            last_leave_time: None,
+        };
+        member.insert(&mut conn).unwrap();
+
+        let first = member.try_enter(&mut conn, 1000, false).unwrap();
+        assert_eq!(first, EnterDecision::Denied { retry_after: 1030 });
+
+        // Still within the lockout window: denied regardless of `authenticated`.
+        let still_locked = member.try_enter(&mut conn, 1010, true).unwrap();
+        assert_eq!(still_locked, EnterDecision::Denied { retry_after: 1030 });
+
+        // Lockout has expired, but the attempt fails again: backoff doubles.
+        let second = member.try_enter(&mut conn, 1031, false).unwrap();
+        assert_eq!(second, EnterDecision::Denied { retry_after: 1031 + 60 });
+    }
+}
@@ -0,0 +1,80 @@
+//! Built-in ed25519 challenge-response authentication for tags.
+
+use ::std::error::Error as StdError;
+use ::std::fmt;
+use ::ed25519_dalek::{PublicKey, Signature};
+
+use ::error::IdentificationError;
+
+/// Reserved `auth_method` value for ed25519 challenge-response authentication.
+pub const AUTH_METHOD_ED25519: u32 = 1;
+
+/// Error returned when ed25519 challenge-response authentication fails.
+#[derive(Debug)]
+pub enum Ed25519Error {
+    /// The tag's `auth_method` wasn't [`AUTH_METHOD_ED25519`].
+    WrongMethod(u32),
+    /// `auth_data` wasn't a 32-byte ed25519 public key.
+    InvalidPublicKey,
+    /// `signature` wasn't a validly-formed ed25519 signature.
+    InvalidSignature,
+    /// The signature didn't verify against the stored public key and challenge.
+    VerificationFailed,
+}
+
+impl StdError for Ed25519Error {
+    fn description(&self) -> &str {
+        match *self {
+            Ed25519Error::WrongMethod(_) => "tag is not registered for ed25519 authentication",
+            Ed25519Error::InvalidPublicKey => "auth_data is not a valid ed25519 public key",
+            Ed25519Error::InvalidSignature => "not a valid ed25519 signature",
+            Ed25519Error::VerificationFailed => "ed25519 signature verification failed",
+        }
+    }
+}
+
+impl fmt::Display for Ed25519Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", StdError::description(self))
+    }
+}
+
+/// Attempts to retrieve a tag from the database and authenticate it via an ed25519
+/// challenge-response: `auth_data` is treated as a 32-byte public key, and `signature` must be a
+/// valid ed25519 signature over `challenge` for it to verify. Tags registered under any other
+/// `auth_method` are rejected.
+///
+/// The caller is responsible for generating `challenge` fresh per attempt (e.g. a random 32-byte
+/// nonce) and having the tag/reader sign it; this function only verifies the result.
+pub fn identify_user_challenge<'conn, 'tag>(
+    connection: &'conn mut ::rusqlite::Connection,
+    tag_id: &'tag [u8],
+    challenge: &[u8],
+    signature: &[u8],
+) -> Result<::Member, IdentificationError<Ed25519Error>> {
+    // `identify_user` skips the callback for tags without `auth_data`; such tags carry no public
+    // key and must not be accepted unverified.
+    let mut verified = false;
+    let member = try!(::identify_user(connection, tag_id, |auth_method, auth_data| {
+        if auth_method != AUTH_METHOD_ED25519 {
+            return Err(Ed25519Error::WrongMethod(auth_method));
+        }
+
+        try!(verify(auth_data, challenge, signature));
+        verified = true;
+        Ok(())
+    }));
+
+    if verified {
+        Ok(member)
+    } else {
+        Err(IdentificationError::TagAuthenticationError(Ed25519Error::InvalidPublicKey))
+    }
+}
+
+fn verify(public_key: &[u8], challenge: &[u8], signature: &[u8]) -> Result<(), Ed25519Error> {
+    let public_key = try!(PublicKey::from_bytes(public_key).map_err(|_| Ed25519Error::InvalidPublicKey));
+    let signature = try!(Signature::from_bytes(signature).map_err(|_| Ed25519Error::InvalidSignature));
+
+    public_key.verify(challenge, &signature).map_err(|_| Ed25519Error::VerificationFailed)
+}
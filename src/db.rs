@@ -0,0 +1,169 @@
+//! Cached-statement wrapper around `Connection`.
+
+use ::rusqlite::Connection;
+
+use ::error::IdentificationError;
+use ::row::FromRow;
+
+/// Wraps a `Connection`, reusing compiled statements for its operations via `prepare_cached`.
+pub struct Db {
+    conn: Connection,
+    /// Whether to actually use the statement cache. Exposed so test code can bypass it (e.g. to
+    /// rule out stale-cache bugs as the cause of a failure).
+    maybe_cached: bool,
+}
+
+impl Db {
+    /// Wraps `conn`, caching prepared statements for its hot operations.
+    pub fn new(conn: Connection) -> Db {
+        Db { conn: conn, maybe_cached: true }
+    }
+
+    /// Wraps `conn`, optionally bypassing the statement cache (every call re-`prepare`s instead).
+    pub fn with_caching(conn: Connection, maybe_cached: bool) -> Db {
+        Db { conn: conn, maybe_cached: maybe_cached }
+    }
+
+    fn with_stmt<R, F>(&self, sql: &str, f: F) -> ::rusqlite::Result<R>
+        where F: FnOnce(&mut ::rusqlite::Statement) -> ::rusqlite::Result<R>
+    {
+        if self.maybe_cached {
+            let mut stmt = try!(self.conn.prepare_cached(sql));
+            f(&mut stmt)
+        } else {
+            let mut stmt = try!(self.conn.prepare(sql));
+            f(&mut stmt)
+        }
+    }
+
+    /// Inserts `member`, same as [`::Member::insert`], reusing a cached statement.
+    pub fn insert_member(&mut self, member: &::Member) -> ::rusqlite::Result<()> {
+        self.with_stmt(
+            "INSERT INTO members (uid, manager, ban_time, last_attempt, max_auto, last_enter, last_leave)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            |stmt| member.exec_stmt(stmt)
+        )
+    }
+
+    /// Updates `member`, same as [`::Member::update`], reusing a cached statement.
+    pub fn update_member(&mut self, member: &::Member) -> ::rusqlite::Result<()> {
+        self.with_stmt(
+            "UPDATE members
+             SET manager = ?2, ban_time = ?3, last_attempt = ?4, max_auto = ?5, last_enter = ?6, last_leave = ?7
+             WHERE uid = ?1",
+            |stmt| member.exec_stmt(stmt)
+        )
+    }
+
+    /// Deletes `member`, same as [`::Member::delete`], reusing a cached statement.
+    pub fn delete_member(&mut self, member: &::Member) -> ::rusqlite::Result<bool> {
+        let uid = member.uid as i64;
+        self.with_stmt(
+            "DELETE FROM members
+             WHERE uid = ?",
+            |stmt| stmt.execute(&[&uid]).map(|n| n == 1)
+        )
+    }
+
+    /// Looks up and authenticates a tag, same as [`::identify_user`], reusing a cached statement
+    /// for the lookup.
+    pub fn identify_user<E, Cb>(&mut self, tag_id: &[u8], mut callback: Cb) -> Result<::Member, IdentificationError<E>>
+        where E: ::std::error::Error, Cb: FnMut(u32, &[u8]) -> Result<(), E>
+    {
+        let result = self.with_stmt(
+            "SELECT m.uid, m.manager, m.ban_time, last_attempt, max_auto, last_enter, last_leave, t.auth_method, t.auth_data
+            FROM tags t
+            INNER JOIN members m
+            ON t.uid = m.uid
+            WHERE t.tag_id = ?",
+            |stmt| {
+                let mut rows = try!(stmt.query_map(&[&tag_id], |row| {
+                    let auth_method = row.get::<_, i64>(7) as u32;
+                    let auth_data = row.get::<_, Option<Vec<u8>>>(8);
+                    auth_data.map_or(Ok(()), |auth_data| callback(auth_method, &auth_data))
+                        .map_err(IdentificationError::TagAuthenticationError)
+                        .and_then(|_| ::Member::from_row(row).map_err(IdentificationError::from))
+                }));
+
+                Ok(rows.next())
+            }
+        );
+
+        try!(try!(result)
+             .ok_or(IdentificationError::TagNotFound)
+             .and_then(|r| r.map_err(Into::into))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_update_delete_cached() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        ::create_tables(&mut conn).unwrap();
+        let mut db = Db::new(conn);
+
+        let member = ::Member {
+            uid: 42,
+            can_manage_users: false,
+            ban_time: None,
+            last_open_attempt: None,
+            max_auto_inactive: 1800,
+            last_enter_time: None,
+            last_leave_time: None,
+        };
+        db.insert_member(&member).unwrap();
+        db.update_member(&member).unwrap();
+        assert!(db.delete_member(&member).unwrap());
+        assert!(!db.delete_member(&member).unwrap());
+    }
+
+    #[test]
+    fn insert_update_delete_uncached() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        ::create_tables(&mut conn).unwrap();
+        let mut db = Db::with_caching(conn, false);
+
+        let member = ::Member {
+            uid: 42,
+            can_manage_users: false,
+            ban_time: None,
+            last_open_attempt: None,
+            max_auto_inactive: 1800,
+            last_enter_time: None,
+            last_leave_time: None,
+        };
+        db.insert_member(&member).unwrap();
+        db.update_member(&member).unwrap();
+        assert!(db.delete_member(&member).unwrap());
+    }
+
+    #[test]
+    fn identify_user_cached() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        ::create_tables(&mut conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO tags (tag_id, uid, auth_method, auth_data) VALUES (?, 42, 0, NULL)",
+            &[&(&[0u8, 1, 2][..])]
+        ).unwrap();
+        let member = ::Member {
+            uid: 42,
+            can_manage_users: false,
+            ban_time: None,
+            last_open_attempt: None,
+            max_auto_inactive: 1800,
+            last_enter_time: None,
+            last_leave_time: None,
+        };
+        member.insert(&mut conn).unwrap();
+
+        let mut db = Db::new(conn);
+        let found = db.identify_user(&[0, 1, 2], |_, _| ::std::result::Result::Ok::<_, ::std::io::Error>(())).unwrap();
+
+        assert_eq!(found, member);
+    }
+}
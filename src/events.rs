@@ -0,0 +1,168 @@
+//! Append-only access event log.
+
+use ::rusqlite::Connection;
+
+pub(crate) fn create_events_table_impl(conn: &Connection) -> ::rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE events (
+         id         INTEGER PRIMARY KEY AUTOINCREMENT,
+         uid        INTEGER NOT NULL,
+         tag_id     VARBINARY(32),
+         event_type INTEGER NOT NULL,
+         timestamp  INTEGER NOT NULL,
+         detail     TEXT
+        )",
+        &[]
+    ).map(|_| ())
+}
+
+/// Kind of access event recorded in the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    /// Member successfully entered.
+    Enter,
+    /// Member successfully left.
+    Exit,
+    /// An entry attempt was denied (e.g. lockout or failed authentication).
+    Denied,
+}
+
+impl EventType {
+    fn to_i64(&self) -> i64 {
+        match *self {
+            EventType::Enter => 0,
+            EventType::Exit => 1,
+            EventType::Denied => 2,
+        }
+    }
+
+    fn from_i64(value: i64) -> Option<EventType> {
+        match value {
+            0 => Some(EventType::Enter),
+            1 => Some(EventType::Exit),
+            2 => Some(EventType::Denied),
+            _ => None,
+        }
+    }
+}
+
+impl ::rusqlite::types::FromSql for EventType {
+    /// Fails instead of panicking when the stored value isn't one of the known discriminants
+    /// (e.g. a column written by a newer crate version, or written out of band).
+    fn column_result(value: ::rusqlite::types::ValueRef) -> ::rusqlite::types::FromSqlResult<Self> {
+        let value = try!(i64::column_result(value));
+        EventType::from_i64(value).ok_or(::rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+/// A single row of the `events` log.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccessEvent {
+    /// Row ID.
+    pub id: i64,
+    /// User ID the event concerns.
+    pub uid: u32,
+    /// Tag used, if the event was tied to one.
+    pub tag_id: Option<Vec<u8>>,
+    /// What happened.
+    pub event_type: EventType,
+    /// When it happened (Unix timestamp).
+    pub timestamp: i64,
+    /// Free-form extra context (e.g. a denial reason).
+    pub detail: Option<String>,
+}
+
+/// Appends an event to the log.
+pub fn log_event(conn: &mut Connection, uid: u32, tag_id: Option<&[u8]>, event_type: EventType, timestamp: i64, detail: Option<&str>) -> ::rusqlite::Result<()> {
+    let mut stmt = try!(conn.prepare(
+        "INSERT INTO events (uid, tag_id, event_type, timestamp, detail)
+         VALUES (?, ?, ?, ?, ?)"
+    ));
+
+    let uid = uid as i64;
+    let event_type = event_type.to_i64();
+    stmt.execute(&[&uid, &tag_id, &event_type, &timestamp, &detail])
+        .map(|_| ())
+}
+
+/// Reads events for `uid` that happened at or after `since`, most recent first, capped at
+/// `limit` rows.
+pub fn events_for_member(conn: &Connection, uid: u32, since: i64, limit: u32) -> ::rusqlite::Result<Vec<AccessEvent>> {
+    let mut stmt = try!(conn.prepare(
+        "SELECT id, uid, tag_id, event_type, timestamp, detail
+         FROM events
+         WHERE uid = ? AND timestamp >= ?
+         ORDER BY timestamp DESC, id DESC
+         LIMIT ?"
+    ));
+
+    let uid_param = uid as i64;
+    let rows = try!(stmt.query_and_then(&[&uid_param, &since, &(limit as i64)], |row| {
+        Ok(AccessEvent {
+            id: row.get(0),
+            uid: row.get::<_, i64>(1) as u32,
+            tag_id: row.get(2),
+            event_type: try!(row.get_checked(3)),
+            timestamp: row.get(4),
+            detail: row.get(5),
+        })
+    }));
+
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn_with_tables() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        ::migrations::create_tables_impl(&conn).unwrap();
+        create_events_table_impl(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn log_and_query_events() {
+        let mut conn = conn_with_tables();
+
+        log_event(&mut conn, 42, None, EventType::Denied, 100, Some("locked out")).unwrap();
+        log_event(&mut conn, 42, Some(&[1, 2, 3][..]), EventType::Enter, 200, None).unwrap();
+        log_event(&mut conn, 7, None, EventType::Enter, 150, None).unwrap();
+
+        let events = events_for_member(&conn, 42, 0, 10).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].event_type, EventType::Enter);
+        assert_eq!(events[0].timestamp, 200);
+        assert_eq!(events[1].event_type, EventType::Denied);
+        assert_eq!(events[1].detail, Some("locked out".to_owned()));
+    }
+
+    #[test]
+    fn events_for_member_respects_since_and_limit() {
+        let mut conn = conn_with_tables();
+
+        for t in 0..5 {
+            log_event(&mut conn, 42, None, EventType::Enter, t, None).unwrap();
+        }
+
+        let events = events_for_member(&conn, 42, 2, 10).unwrap();
+        assert_eq!(events.len(), 3);
+
+        let events = events_for_member(&conn, 42, 0, 2).unwrap();
+        assert_eq!(events.len(), 2);
+    }
+
+    #[test]
+    fn events_for_member_errors_instead_of_panicking_on_unknown_event_type() {
+        let conn = conn_with_tables();
+
+        conn.execute(
+            "INSERT INTO events (uid, tag_id, event_type, timestamp, detail) VALUES (42, NULL, 99, 100, NULL)",
+            &[]
+        ).unwrap();
+
+        assert!(events_for_member(&conn, 42, 0, 10).is_err());
+    }
+}
@@ -1,7 +1,32 @@
 extern crate rusqlite;
+extern crate ed25519_dalek;
+#[cfg(test)]
+extern crate rand;
+#[cfg(feature = "pool")]
+extern crate r2d2;
+#[cfg(feature = "pool")]
+extern crate r2d2_sqlite;
 
 use ::std::borrow::Cow;
 
+mod migrations;
+mod auth;
+mod access;
+mod events;
+mod row;
+#[cfg(feature = "pool")]
+mod pool;
+mod db;
+
+pub use migrations::{Migration, default_migrations, open_database};
+pub use auth::{AUTH_METHOD_ED25519, Ed25519Error, identify_user_challenge};
+pub use access::EnterDecision;
+pub use events::{AccessEvent, EventType, log_event, events_for_member};
+pub use row::{FromRow, query_one};
+#[cfg(feature = "pool")]
+pub use pool::{Pool, Error as PoolError, PoolIdentificationError};
+pub use db::Db;
+
 /// Contains information about member of Progressbar hackerspace.
 #[derive(Eq, PartialEq, Debug)]
 pub struct Member {
@@ -72,6 +97,17 @@ impl Member {
         stmt.execute(&[&uid])
             .map(|n| n == 1)
     }
+
+    /// Loads the `Member` with the given `uid`, if one exists.
+    pub fn load(conn: &::rusqlite::Connection, uid: u32) -> ::rusqlite::Result<Option<Member>> {
+        let mut stmt = try!(conn.prepare(
+            "SELECT uid, manager, ban_time, last_attempt, max_auto, last_enter, last_leave
+             FROM members
+             WHERE uid = ?"
+        ));
+
+        ::row::query_one(&mut stmt, &[&(uid as i64)])
+    }
 }
 
 /// Contains data about tag. Used for insertion only (because not all fields are needed for
@@ -82,9 +118,10 @@ pub struct Tag<'id, 'adata> {
     /// User ID. Same as on Progressbar website.
     pub uid: u32,
     /// Code for method of authentication. Purposefully not enum, because it's meant to be a raw
-    /// type.
+    /// type. [`AUTH_METHOD_ED25519`] is reserved for the built-in challenge-response method.
     pub auth_method: u32,
     /// Arbitrary data if needed for authentication. (None means no additional authentication.)
+    /// For [`AUTH_METHOD_ED25519`] this is the tag's 32-byte ed25519 public key.
     pub auth_data: Cow<'adata, [u8]>,
 }
 
@@ -146,7 +183,7 @@ use error::IdentificationError;
 pub fn identify_user<'conn, 'tag, E, Cb>(connection: &'conn mut rusqlite::Connection, tag_id: &'tag [u8], mut callback: Cb) -> Result<Member, IdentificationError<E>> where E: ::std::error::Error, Cb: FnMut(u32, &[u8]) -> Result<(), E> {
     let mut stmt = try!(
         connection.prepare(
-            "SELECT t.auth_method, t.auth_data, m.uid, m.manager, m.ban_time, last_attempt, max_auto, last_enter, last_leave
+            "SELECT m.uid, m.manager, m.ban_time, last_attempt, max_auto, last_enter, last_leave, t.auth_method, t.auth_data
             FROM tags t
             INNER JOIN members m
             ON t.uid = m.uid
@@ -154,17 +191,12 @@ pub fn identify_user<'conn, 'tag, E, Cb>(connection: &'conn mut rusqlite::Connec
         )
     );
     let mut rows = try!(stmt.query_map(&[&tag_id], |row| {
-        let auth_data = row.get::<_, Option<Vec<u8>>>(1);
+        let auth_method = row.get::<_, i64>(7) as u32;
+        let auth_data = row.get::<_, Option<Vec<u8>>>(8);
                       // If auth data isn't present we skip additional tag authentication.
-        auth_data.map_or(Ok(()), |auth_data| { callback(row.get::<_, i64>(0) as u32, &auth_data) }).map(|_| Member {
-            uid: row.get::<_, i64>(2) as u32,
-            can_manage_users: row.get(3),
-            ban_time: row.get(4),
-            last_open_attempt: row.get(5),
-            max_auto_inactive: row.get(6),
-            last_enter_time: row.get(7),
-            last_leave_time: row.get(8),
-        }).map_err(IdentificationError::TagAuthenticationError)
+        auth_data.map_or(Ok(()), |auth_data| callback(auth_method, &auth_data))
+            .map_err(IdentificationError::TagAuthenticationError)
+            .and_then(|_| Member::from_row(row).map_err(IdentificationError::from))
     }));
 
     try!(rows
@@ -175,29 +207,13 @@ pub fn identify_user<'conn, 'tag, E, Cb>(connection: &'conn mut rusqlite::Connec
 }
 
 /// Creates needed tables for Heimdall to work.
+///
+/// This is equivalent to migration 1 of [`default_migrations`] and is kept around for embedded
+/// use against a connection that's already open. New code that wants schema evolution (e.g.
+/// hackerspace deployments adding permission flags down the line) should prefer
+/// [`open_database`], which runs this plus any later migrations and tracks the schema version.
 pub fn create_tables(conn: &mut ::rusqlite::Connection) -> ::rusqlite::Result<()> {
-        try!(conn.execute(
-            "CREATE TABLE tags (
-             tag_id VARBINARY(32) NOT NULL PRIMARY KEY,
-             uid    INTEGER NOT NULL,
-             auth_method INTEGER,
-             auth_data BLOB
-            )",
-            &[]
-        ));
-
-        conn.execute(
-            "CREATE TABLE members (
-             uid          INTEGER NOT NULL PRIMARY KEY,
-             manager      BOOLEAN NOT NULL,
-             ban_time     INTEGER,
-             last_attempt INTEGER,
-             max_auto     INTEGER NOT NULL,
-             last_enter   INTEGER,
-             last_leave   INTEGER
-            )",
-            &[]
-        ).map(|_| ())
+    migrations::create_tables_impl(conn)
 }
 
 #[cfg(test)]
@@ -208,6 +224,36 @@ mod tests {
         ::create_tables(&mut conn).unwrap();
     }
 
+    #[test]
+    fn identify_challenge_ed25519() {
+        use ::ed25519_dalek::Keypair;
+        use ::rand::rngs::OsRng;
+
+        let mut conn = ::rusqlite::Connection::open_in_memory().unwrap();
+        ::create_tables(&mut conn).unwrap();
+
+        let mut csprng = OsRng::new().unwrap();
+        let keypair = Keypair::generate(&mut csprng);
+        let tag = [9, 9, 9];
+        let challenge = [7u8; 32];
+        let signature = keypair.sign(&challenge);
+
+        conn.execute(
+            "INSERT INTO tags (tag_id, uid, auth_method, auth_data)
+             VALUES (?, 42, ?, ?)",
+            &[&(&tag as &[u8]), &(::AUTH_METHOD_ED25519 as i64), &(&keypair.public.to_bytes()[..] as &[u8])]
+        ).unwrap();
+
+        conn.execute(
+            "INSERT INTO members (uid, manager, ban_time, last_attempt, max_auto, last_enter, last_leave)
+             VALUES (42, 0, NULL, NULL, 1800, NULL, NULL)",
+            &[]
+        ).unwrap();
+
+        let member = ::identify_user_challenge(&mut conn, &tag, &challenge, &signature.to_bytes()).unwrap();
+        assert_eq!(member.uid, 42);
+    }
+
     #[test]
     fn identify() {
         let mut conn = ::rusqlite::Connection::open_in_memory().unwrap();
@@ -266,4 +312,25 @@ mod tests {
         member.insert(&mut conn).unwrap();
         member.delete(&mut conn).unwrap();
     }
+
+    #[test]
+    fn load() {
+        let mut conn = ::rusqlite::Connection::open_in_memory().unwrap();
+        ::create_tables(&mut conn).unwrap();
+
+        assert_eq!(::Member::load(&conn, 42).unwrap(), None);
+
+        let member = ::Member {
+            uid: 42,
+            can_manage_users: false,
+            ban_time: None,
+            last_open_attempt: None,
+            max_auto_inactive: 1800,
+            last_enter_time: None,
+            last_leave_time: None,
+        };
+        member.insert(&mut conn).unwrap();
+
+        assert_eq!(::Member::load(&conn, 42).unwrap(), Some(member));
+    }
 }
@@ -0,0 +1,195 @@
+//! Schema versioning and migrations.
+//!
+//! Schema changes are expressed as an ordered list of `Migration`s instead of a single
+//! `CREATE TABLE` pass, so an existing deployment's database can be brought up to date in place
+//! instead of requiring manual SQL surgery (or a wipe) every time a column is added.
+
+use ::rusqlite::{Connection, Transaction};
+
+/// A single schema change, identified by the `version` it brings the database to.
+///
+/// Migrations are applied in ascending `version` order, each inside the same transaction, and
+/// `version` is persisted via `PRAGMA user_version` once they all succeed.
+pub struct Migration {
+    /// Schema version this migration results in once applied.
+    pub version: i64,
+    /// Applies the migration. Receives the open transaction so several migrations can be run
+    /// and rolled back together.
+    pub run: fn(&Transaction) -> ::rusqlite::Result<()>,
+}
+
+/// Creates the `tags` and `members` tables. This is migration 1 (the lowest version registered
+/// by [`default_migrations`]) and exists so fresh databases and the legacy [`::create_tables`]
+/// produce an identical schema.
+fn migrate_initial_schema(tx: &Transaction) -> ::rusqlite::Result<()> {
+    create_tables_impl(tx)
+}
+
+/// Adds the `events` access log table.
+fn migrate_events_table(tx: &Transaction) -> ::rusqlite::Result<()> {
+    ::events::create_events_table_impl(tx)
+}
+
+pub(crate) fn create_tables_impl(conn: &Connection) -> ::rusqlite::Result<()> {
+    try!(conn.execute(
+        "CREATE TABLE tags (
+         tag_id VARBINARY(32) NOT NULL PRIMARY KEY,
+         uid    INTEGER NOT NULL,
+         auth_method INTEGER,
+         auth_data BLOB
+        )",
+        &[]
+    ));
+
+    conn.execute(
+        "CREATE TABLE members (
+         uid          INTEGER NOT NULL PRIMARY KEY,
+         manager      BOOLEAN NOT NULL,
+         ban_time     INTEGER,
+         last_attempt INTEGER,
+         max_auto     INTEGER NOT NULL,
+         last_enter   INTEGER,
+         last_leave   INTEGER
+        )",
+        &[]
+    ).map(|_| ())
+}
+
+/// The migrations shipped with this version of the crate. Applications that only need the
+/// built-in schema can pass this straight to [`open_database`]; ones with extra tables of their
+/// own can append additional `Migration`s with higher `version`s.
+pub fn default_migrations() -> Vec<Migration> {
+    vec![
+        Migration { version: 1, run: migrate_initial_schema },
+        Migration { version: 2, run: migrate_events_table },
+    ]
+}
+
+fn schema_version(conn: &Connection) -> ::rusqlite::Result<i64> {
+    conn.query_row("PRAGMA user_version", &[], |row| row.get(0))
+}
+
+fn set_schema_version(conn: &Connection, version: i64) -> ::rusqlite::Result<()> {
+    // PRAGMA doesn't support bound parameters; `version` always comes from our own migration
+    // list, never from untrusted input.
+    conn.execute(&format!("PRAGMA user_version = {}", version), &[]).map(|_| ())
+}
+
+fn table_exists(conn: &Connection, name: &str) -> ::rusqlite::Result<bool> {
+    match conn.query_row("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?", &[&name], |_| ()) {
+        Ok(()) => Ok(true),
+        Err(::rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// A fresh `sqlite` file and a pre-migrations database (one `create_tables()` was called on
+/// directly, e.g. a live door-controller DB predating this subsystem) are indistinguishable by
+/// `PRAGMA user_version` alone — both read back as 0. Since the latter already has migration 1's
+/// tables, blindly trusting `user_version` would re-run `CREATE TABLE tags/members` against it
+/// and fail. If `tags`/`members` already exist, treat the database as already being at version 1
+/// so it isn't re-applied.
+fn current_version(conn: &Connection) -> ::rusqlite::Result<i64> {
+    let version = try!(schema_version(conn));
+    if version == 0 && try!(table_exists(conn, "tags")) && try!(table_exists(conn, "members")) {
+        Ok(1)
+    } else {
+        Ok(version)
+    }
+}
+
+/// Opens the database at `path`, bringing its schema up to date with `migrations`.
+///
+/// Reads the current `PRAGMA user_version` (falling back to schema inspection for a database
+/// that predates this subsystem, see [`current_version`]), runs every migration with a higher
+/// version (in ascending order) inside a single transaction, and bumps the stored version to the
+/// highest one applied. If any migration fails the whole transaction is rolled back and the
+/// database is left untouched.
+pub fn open_database(path: &str, migrations: &[Migration]) -> ::rusqlite::Result<Connection> {
+    let mut conn = try!(Connection::open(path));
+    let stored_version = try!(schema_version(&conn));
+    let current = try!(current_version(&conn));
+
+    let mut pending: Vec<&Migration> = migrations.iter().filter(|m| m.version > current).collect();
+    pending.sort_by_key(|m| m.version);
+
+    let target_version = pending.iter().map(|m| m.version).max().unwrap_or(current);
+
+    // Either there are migrations to run, or a legacy database was just bootstrapped to a known
+    // version that `PRAGMA user_version` doesn't reflect yet — either way, persist it.
+    if !pending.is_empty() || target_version != stored_version {
+        let tx = try!(conn.transaction());
+
+        for migration in &pending {
+            try!((migration.run)(&tx));
+        }
+
+        try!(set_schema_version(&tx, target_version));
+        try!(tx.commit());
+    }
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_database_runs_default_migrations() {
+        let conn = open_database(":memory:", &default_migrations()).unwrap();
+
+        // Tables from migration 1 must exist, and the version must be persisted.
+        conn.execute("INSERT INTO members (uid, manager, ban_time, last_attempt, max_auto, last_enter, last_leave) VALUES (1, 0, NULL, NULL, 1800, NULL, NULL)", &[]).unwrap();
+        conn.execute("INSERT INTO events (uid, tag_id, event_type, timestamp, detail) VALUES (1, NULL, 0, 1000, NULL)", &[]).unwrap();
+        let version: i64 = conn.query_row("PRAGMA user_version", &[], |row| row.get(0)).unwrap();
+        assert_eq!(version, 2);
+    }
+
+    #[test]
+    fn open_database_bootstraps_legacy_create_tables_database() {
+        // Simulates a pre-existing door-controller DB that was set up by the bare
+        // `create_tables()` and never had `PRAGMA user_version` touched, i.e. it still reads 0
+        // even though migration 1's tables already exist.
+        let path = ::std::env::temp_dir().join(format!("heimdall_db_legacy_bootstrap_test_{}.sqlite", ::std::process::id()));
+        let path_str = path.to_str().unwrap().to_owned();
+        let _ = ::std::fs::remove_file(&path);
+
+        {
+            let conn = Connection::open(&path_str).unwrap();
+            create_tables_impl(&conn).unwrap();
+        }
+
+        let conn = open_database(&path_str, &default_migrations()).unwrap();
+
+        let version: i64 = conn.query_row("PRAGMA user_version", &[], |row| row.get(0)).unwrap();
+        assert_eq!(version, 2);
+        conn.execute("INSERT INTO events (uid, tag_id, event_type, timestamp, detail) VALUES (1, NULL, 0, 1000, NULL)", &[]).unwrap();
+
+        drop(conn);
+        let _ = ::std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn open_database_skips_already_applied_migrations() {
+        let path = ::std::env::temp_dir().join(format!("heimdall_db_skip_applied_test_{}.sqlite", ::std::process::id()));
+        let path_str = path.to_str().unwrap().to_owned();
+        let _ = ::std::fs::remove_file(&path);
+
+        {
+            let conn = Connection::open(&path_str).unwrap();
+            create_tables_impl(&conn).unwrap();
+            ::events::create_events_table_impl(&conn).unwrap();
+            set_schema_version(&conn, 2).unwrap();
+        }
+
+        // A migration bumping to the same version must not run again (it would fail, since the
+        // tables already exist).
+        let conn = open_database(&path_str, &default_migrations()).unwrap();
+        let version = schema_version(&conn).unwrap();
+        assert_eq!(version, 2);
+
+        drop(conn);
+        let _ = ::std::fs::remove_file(&path);
+    }
+}
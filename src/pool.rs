@@ -0,0 +1,143 @@
+//! Pooled, multi-threaded access via `r2d2`.
+
+use ::std::error::Error as StdError;
+use ::std::fmt;
+
+use ::r2d2::Pool as R2d2Pool;
+use ::r2d2_sqlite::SqliteConnectionManager;
+
+use ::error::IdentificationError;
+
+/// Error checking out or using a pooled connection.
+#[derive(Debug)]
+pub enum Error {
+    /// Couldn't check out a connection from the pool.
+    Pool(::r2d2::Error),
+    /// The operation itself failed once a connection was obtained.
+    Sqlite(::rusqlite::Error),
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        "pooled database operation failed"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Pool(ref e) => Some(e),
+            Error::Sqlite(ref e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.description(), self.cause().unwrap())
+    }
+}
+
+impl From<::r2d2::Error> for Error {
+    fn from(err: ::r2d2::Error) -> Self {
+        Error::Pool(err)
+    }
+}
+
+impl From<::rusqlite::Error> for Error {
+    fn from(err: ::rusqlite::Error) -> Self {
+        Error::Sqlite(err)
+    }
+}
+
+/// Error from [`Pool::identify_user`]: either the pool or the identification itself failed.
+#[derive(Debug)]
+pub enum PoolIdentificationError<E: StdError> {
+    /// Couldn't check out a connection from the pool.
+    Pool(::r2d2::Error),
+    /// Identification failed once a connection was obtained.
+    Identification(IdentificationError<E>),
+}
+
+impl<E: StdError> StdError for PoolIdentificationError<E> {
+    fn description(&self) -> &str {
+        "failed to identify user via pooled connection"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            PoolIdentificationError::Pool(ref e) => Some(e),
+            PoolIdentificationError::Identification(ref e) => Some(e),
+        }
+    }
+}
+
+impl<E: StdError> fmt::Display for PoolIdentificationError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.description(), self.cause().unwrap())
+    }
+}
+
+impl<E: StdError> From<::r2d2::Error> for PoolIdentificationError<E> {
+    fn from(err: ::r2d2::Error) -> Self {
+        PoolIdentificationError::Pool(err)
+    }
+}
+
+impl<E: StdError> From<IdentificationError<E>> for PoolIdentificationError<E> {
+    fn from(err: IdentificationError<E>) -> Self {
+        PoolIdentificationError::Identification(err)
+    }
+}
+
+/// A pooled handle to the database. Cheap to clone and safe to share between threads.
+#[derive(Clone)]
+pub struct Pool {
+    pool: R2d2Pool<SqliteConnectionManager>,
+}
+
+impl Pool {
+    /// Opens `path`, bringing its schema up to date with `migrations` (see [`::open_database`]),
+    /// enables WAL mode, and builds a connection pool over it.
+    pub fn open(path: &str, migrations: &[::Migration]) -> Result<Pool, Error> {
+        // Run migrations through a plain connection first so the schema is settled before any
+        // pooled connection is handed out.
+        try!(::open_database(path, migrations));
+
+        let manager = SqliteConnectionManager::file(path)
+            .with_init(|conn| conn.execute_batch("PRAGMA journal_mode = WAL;"));
+        let pool = try!(R2d2Pool::new(manager));
+
+        Ok(Pool { pool: pool })
+    }
+
+    /// Looks up and authenticates a tag, same as [`::identify_user`], using a pooled connection.
+    pub fn identify_user<E, Cb>(&self, tag_id: &[u8], callback: Cb) -> Result<::Member, PoolIdentificationError<E>>
+        where E: StdError, Cb: FnMut(u32, &[u8]) -> Result<(), E>
+    {
+        let mut conn = try!(self.pool.get());
+        ::identify_user(&mut conn, tag_id, callback).map_err(Into::into)
+    }
+
+    /// Loads a `Member` by uid, same as [`::Member::load`], using a pooled connection.
+    pub fn load_member(&self, uid: u32) -> Result<Option<::Member>, Error> {
+        let conn = try!(self.pool.get());
+        ::Member::load(&conn, uid).map_err(Into::into)
+    }
+
+    /// Inserts a `Member`, same as [`::Member::insert`], using a pooled connection.
+    pub fn insert_member(&self, member: &::Member) -> Result<(), Error> {
+        let mut conn = try!(self.pool.get());
+        member.insert(&mut conn).map_err(Into::into)
+    }
+
+    /// Updates a `Member`, same as [`::Member::update`], using a pooled connection.
+    pub fn update_member(&self, member: &::Member) -> Result<(), Error> {
+        let mut conn = try!(self.pool.get());
+        member.update(&mut conn).map_err(Into::into)
+    }
+
+    /// Deletes a `Member`, same as [`::Member::delete`], using a pooled connection.
+    pub fn delete_member(&self, member: &::Member) -> Result<bool, Error> {
+        let mut conn = try!(self.pool.get());
+        member.delete(&mut conn).map_err(Into::into)
+    }
+}
@@ -0,0 +1,78 @@
+//! Generic row decoding.
+
+use ::rusqlite::{Row, Statement};
+use ::rusqlite::types::ToSql;
+
+/// Types that can be built from a single `rusqlite` row.
+pub trait FromRow: Sized {
+    /// Decodes `Self` from `row`.
+    fn from_row(row: &Row) -> ::rusqlite::Result<Self>;
+}
+
+impl FromRow for ::Member {
+    /// Expects columns in the order `uid, manager, ban_time, last_attempt, max_auto, last_enter,
+    /// last_leave`.
+    fn from_row(row: &Row) -> ::rusqlite::Result<Self> {
+        Ok(::Member {
+            uid: try!(row.get_checked::<_, i64>(0)) as u32,
+            can_manage_users: try!(row.get_checked(1)),
+            ban_time: try!(row.get_checked(2)),
+            last_open_attempt: try!(row.get_checked(3)),
+            max_auto_inactive: try!(row.get_checked(4)),
+            last_enter_time: try!(row.get_checked(5)),
+            last_leave_time: try!(row.get_checked(6)),
+        })
+    }
+}
+
+macro_rules! impl_from_row_for_tuple {
+    ($($idx:tt => $ty:ident),+) => {
+        impl<$($ty),+> FromRow for ($($ty,)+) where $($ty: ::rusqlite::types::FromSql),+ {
+            fn from_row(row: &Row) -> ::rusqlite::Result<Self> {
+                Ok(($(try!(row.get_checked::<_, $ty>($idx)),)+))
+            }
+        }
+    }
+}
+
+impl_from_row_for_tuple!(0 => A);
+impl_from_row_for_tuple!(0 => A, 1 => B);
+impl_from_row_for_tuple!(0 => A, 1 => B, 2 => C);
+
+/// Runs `stmt` with `params` and decodes at most one row as `T`.
+pub fn query_one<T: FromRow>(stmt: &mut Statement, params: &[&ToSql]) -> ::rusqlite::Result<Option<T>> {
+    let mut rows = try!(stmt.query_and_then(params, T::from_row));
+
+    match rows.next() {
+        Some(row) => row.map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_one_decodes_a_tuple() {
+        let conn = ::rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (a INTEGER, b TEXT)", &[]).unwrap();
+        conn.execute("INSERT INTO t (a, b) VALUES (1, 'x')", &[]).unwrap();
+
+        let mut stmt = conn.prepare("SELECT a, b FROM t WHERE a = ?").unwrap();
+        let row: Option<(i64, String)> = query_one(&mut stmt, &[&1i64]).unwrap();
+
+        assert_eq!(row, Some((1, "x".to_owned())));
+    }
+
+    #[test]
+    fn query_one_returns_none_when_no_rows_match() {
+        let conn = ::rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE t (a INTEGER)", &[]).unwrap();
+
+        let mut stmt = conn.prepare("SELECT a FROM t WHERE a = ?").unwrap();
+        let row: Option<(i64,)> = query_one(&mut stmt, &[&1i64]).unwrap();
+
+        assert_eq!(row, None);
+    }
+}